@@ -0,0 +1,158 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Rejects borrows of, and direct mutation through, fields of a
+//! `#[rustc_layout_scalar_valid_range_start]` / `_end` type when those
+//! fields may be interior-mutable, inside `const fn` and other const
+//! contexts.
+//!
+//! The const evaluator and the optimizers both rely on the declared
+//! valid range of a layout-constrained type (e.g. `NonZero<T>`) never
+//! changing once the value has been constructed. A field that is
+//! `!Freeze` defeats that assumption: a long-lived borrow of the
+//! field could be used to write a value outside the declared range
+//! without going through the `unsafe` constructor again. We therefore
+//! treat any borrow of, or assignment through, such a field as a hard
+//! error rather than leaving it to be caught incidentally elsewhere.
+
+use rustc::hir::def_id::DefId;
+use rustc::mir::visit::{PlaceContext, Visitor};
+use rustc::mir::{Location, Mir, Place, ProjectionElem, Rvalue};
+use rustc::ty::{self, Ty, TyCtxt};
+use syntax::symbol::sym;
+
+/// The two ways a const body can observe a layout-constrained,
+/// interior-mutable field that it must not be allowed to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LayoutConstrainedFieldViolation {
+    /// `&x.0` or `&mut x.0`.
+    BorrowOfLayoutConstrainedField,
+    /// `x.0 = ...` (or any other direct mutation through the place).
+    MutationOfLayoutConstrainedField,
+}
+
+/// Walks `mir`, looking for borrows of or mutation through fields
+/// whose base type is layout-constrained (carries a
+/// `#[rustc_layout_scalar_valid_range_start]` or `_end` attribute) and
+/// whose field type is not `Freeze`. Each such place is reported as a
+/// hard error, since neither form is sound in a const context.
+///
+/// Outside of const evaluation this pattern is exactly what `Cell` and
+/// friends are for, so this check only runs over `const fn`, `const`,
+/// and `static` bodies; it is a no-op for ordinary runtime code.
+pub fn check_layout_constrained_fields<'a, 'tcx>(
+    tcx: TyCtxt<'a, 'tcx, 'tcx>,
+    mir: &Mir<'tcx>,
+    mir_def_id: DefId,
+) {
+    if !is_const_context(tcx, mir_def_id) {
+        return;
+    }
+
+    let param_env = tcx.param_env(mir_def_id);
+    let mut checker = LayoutConstrainedFieldChecker { tcx, mir, param_env };
+    checker.visit_mir(mir);
+}
+
+fn is_const_context(tcx: TyCtxt<'_, '_, '_>, def_id: DefId) -> bool {
+    tcx.is_const_fn(def_id)
+        || match tcx.def_key(def_id).disambiguated_data.data {
+            rustc::hir::map::DefPathData::Const | rustc::hir::map::DefPathData::Static => true,
+            _ => false,
+        }
+}
+
+struct LayoutConstrainedFieldChecker<'a, 'tcx> {
+    tcx: TyCtxt<'a, 'tcx, 'tcx>,
+    mir: &'a Mir<'tcx>,
+    param_env: ty::ParamEnv<'tcx>,
+}
+
+impl<'a, 'tcx> LayoutConstrainedFieldChecker<'a, 'tcx> {
+    /// If `place` projects into a field of a layout-constrained,
+    /// interior-mutable type, returns the field's type and the
+    /// `DefId` of the constrained struct so the caller can build a
+    /// diagnostic.
+    fn layout_constrained_field(&self, place: &Place<'tcx>) -> Option<(Ty<'tcx>, DefId)> {
+        let mut place = place;
+        while let Place::Projection(proj) = place {
+            if let ProjectionElem::Field(_, field_ty) = proj.elem {
+                let base_ty = proj.base.ty(self.mir, self.tcx).to_ty(self.tcx);
+                if let ty::Adt(adt_def, _) = base_ty.sty {
+                    if self.is_layout_scalar_valid_range(adt_def.did)
+                        && !field_ty.is_freeze(self.tcx, self.param_env, self.mir.span)
+                    {
+                        return Some((field_ty, adt_def.did));
+                    }
+                }
+            }
+            place = &proj.base;
+        }
+        None
+    }
+
+    fn is_layout_scalar_valid_range(&self, did: DefId) -> bool {
+        self.tcx
+            .get_attrs(did)
+            .iter()
+            .any(|attr| {
+                attr.check_name(sym::rustc_layout_scalar_valid_range_start)
+                    || attr.check_name(sym::rustc_layout_scalar_valid_range_end)
+            })
+    }
+
+    fn report(&self, location: Location, violation: LayoutConstrainedFieldViolation, did: DefId) {
+        let span = self.mir.source_info(location).span;
+        let msg = match violation {
+            LayoutConstrainedFieldViolation::BorrowOfLayoutConstrainedField => {
+                "borrow of layout constrained field with interior mutability"
+            }
+            LayoutConstrainedFieldViolation::MutationOfLayoutConstrainedField => {
+                "mutation of layout constrained field with interior mutability"
+            }
+        };
+        self.tcx
+            .sess
+            .struct_span_err(span, msg)
+            .span_note(
+                self.tcx.def_span(did),
+                "the field's enclosing type is layout constrained here",
+            )
+            .emit();
+    }
+}
+
+impl<'a, 'tcx> Visitor<'tcx> for LayoutConstrainedFieldChecker<'a, 'tcx> {
+    fn visit_rvalue(&mut self, rvalue: &Rvalue<'tcx>, location: Location) {
+        if let Rvalue::Ref(_, _, place) = rvalue {
+            if let Some((_, did)) = self.layout_constrained_field(place) {
+                self.report(
+                    location,
+                    LayoutConstrainedFieldViolation::BorrowOfLayoutConstrainedField,
+                    did,
+                );
+            }
+        }
+        self.super_rvalue(rvalue, location);
+    }
+
+    fn visit_place(&mut self, place: &Place<'tcx>, context: PlaceContext<'tcx>, location: Location) {
+        if context.is_mutating_use() && !context.is_borrow() {
+            if let Some((_, did)) = self.layout_constrained_field(place) {
+                self.report(
+                    location,
+                    LayoutConstrainedFieldViolation::MutationOfLayoutConstrainedField,
+                    did,
+                );
+            }
+        }
+        self.super_place(place, context, location);
+    }
+}
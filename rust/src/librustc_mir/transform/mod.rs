@@ -0,0 +1,41 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! MIR-level safety checks that run once per body, independent of the
+//! optimization pipeline.
+
+pub mod check_layout_constrained_fields;
+
+use rustc::hir::def_id::DefId;
+use rustc::mir::Mir;
+use rustc::ty::TyCtxt;
+
+/// Runs the MIR-level safety checks that aren't folded into the
+/// `Qualifier` walk used for promotion and const-qualification.
+/// Called once per body right after MIR building; each individual
+/// check is responsible for filtering down to the bodies it cares
+/// about (e.g. `check_layout_constrained_fields` only looks at
+/// `const`, `static`, and `const fn` bodies).
+pub fn check_bodies<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>, mir: &Mir<'tcx>, mir_def_id: DefId) {
+    check_layout_constrained_fields::check_layout_constrained_fields(tcx, mir, mir_def_id);
+}
+
+/// Eagerly forces `check_bodies` over every item with a body in the
+/// crate. This mirrors the way `check_unsafety::check_unsafety` walks
+/// `tcx.hir().krate().body_ids` to force its own per-body query, and
+/// runs from the same point in the driver, right after MIR building,
+/// so none of these checks are left as dead code that nothing calls.
+pub fn check_crate<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>) {
+    for &body_id in &tcx.hir().krate().body_ids {
+        let mir_def_id = tcx.hir().body_owner_def_id(body_id);
+        let mir = tcx.mir_built(mir_def_id).borrow();
+        check_bodies(tcx, &mir, mir_def_id);
+    }
+}
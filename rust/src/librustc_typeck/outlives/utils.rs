@@ -11,24 +11,48 @@
 use rustc::ty::outlives::Component;
 use rustc::ty::subst::{Kind, UnpackedKind};
 use rustc::ty::{self, Region, RegionKind, Ty, TyCtxt};
-use std::collections::BTreeSet;
+use rustc_data_structures::fx::FxHashMap;
+use smallvec::SmallVec;
 
 /// Tracks the `T: 'a` or `'a: 'a` predicates that we have inferred
-/// must be added to the struct header.
-pub type RequiredPredicates<'tcx> = BTreeSet<ty::OutlivesPredicate<Kind<'tcx>, ty::Region<'tcx>>>;
+/// must be added to the struct header, grouped by the component (`T`
+/// or `'b`) that must outlive each region in the associated vector.
+/// Grouping by component lets us dedup cheaply and avoids re-sorting
+/// whole predicate tuples on every iteration of the fixed-point loop
+/// in `implicit_infer`.
+pub type RequiredPredicates<'tcx> = FxHashMap<Kind<'tcx>, SmallVec<[Region<'tcx>; 1]>>;
+
+/// Converts the component-keyed map back into the flat set of
+/// `T: 'a` / `'b: 'a` predicates expected as the result of the
+/// `inferred_outlives_of` query.
+pub fn required_predicates_to_predicates<'tcx>(
+    required_predicates: &RequiredPredicates<'tcx>,
+) -> Vec<ty::OutlivesPredicate<Kind<'tcx>, Region<'tcx>>> {
+    required_predicates
+        .iter()
+        .flat_map(|(&kind, regions)| {
+            regions
+                .iter()
+                .map(move |&outlived_region| ty::OutlivesPredicate(kind, outlived_region))
+        })
+        .collect()
+}
 
 /// Given a requirement `T: 'a` or `'b: 'a`, deduce the
-/// outlives_component and add it to `required_predicates`
+/// outlives_component and add it to `required_predicates`. Returns
+/// `true` if this added a new predicate that wasn't already present,
+/// which the fixed-point loop in `implicit_infer` uses to know when
+/// to stop iterating.
 pub fn insert_outlives_predicate<'tcx>(
     tcx: TyCtxt<'_, 'tcx, 'tcx>,
     kind: Kind<'tcx>,
     outlived_region: Region<'tcx>,
     required_predicates: &mut RequiredPredicates<'tcx>,
-) {
+) -> bool {
     // If the `'a` region is bound within the field type itself, we
     // don't want to propagate this constraint to the header.
     if !is_free_region(outlived_region) {
-        return;
+        return false;
     }
 
     match kind.unpack() {
@@ -40,94 +64,188 @@ pub fn insert_outlives_predicate<'tcx>(
             //
             // Or if within `struct Foo<U>` you had `T = Vec<U>`, then
             // we would want to add `U: 'outlived_region`
-            for component in tcx.outlives_components(ty) {
-                match component {
-                    Component::Region(r) => {
-                        // This would arise from something like:
-                        //
-                        // ```
-                        // struct Foo<'a, 'b> {
-                        //    x:  &'a &'b u32
-                        // }
-                        // ```
-                        //
-                        // Here `outlived_region = 'a` and `kind = &'b
-                        // u32`.  Decomposing `&'b u32` into
-                        // components would yield `'b`, and we add the
-                        // where clause that `'b: 'a`.
-                        insert_outlives_predicate(
-                            tcx,
-                            r.into(),
-                            outlived_region,
-                            required_predicates,
-                        );
-                    }
+            insert_outlives_predicate_for_components(tcx, ty, outlived_region, required_predicates)
+        }
 
-                    Component::Param(param_ty) => {
-                        // param_ty: ty::ParamTy
-                        // This would arise from something like:
-                        //
-                        // ```
-                        // struct Foo<'a, U> {
-                        //    x:  &'a Vec<U>
-                        // }
-                        // ```
-                        //
-                        // Here `outlived_region = 'a` and `kind =
-                        // Vec<U>`.  Decomposing `Vec<U>` into
-                        // components would yield `U`, and we add the
-                        // where clause that `U: 'a`.
-                        let ty: Ty<'tcx> = param_ty.to_ty(tcx);
-                        required_predicates
-                            .insert(ty::OutlivesPredicate(ty.into(), outlived_region));
-                    }
+        UnpackedKind::Lifetime(r) => {
+            if !is_free_region(r) {
+                return false;
+            }
+            insert_required_predicate(kind, outlived_region, required_predicates)
+        }
 
-                    Component::Projection(proj_ty) => {
-                        // This would arise from something like:
-                        //
-                        // ```
-                        // struct Foo<'a, T: Iterator> {
-                        //    x:  &'a <T as Iterator>::Item
-                        // }
-                        // ```
-                        //
-                        // Here we want to add an explicit `where <T as Iterator>::Item: 'a`.
-                        let ty: Ty<'tcx> = tcx.mk_projection(proj_ty.item_def_id, proj_ty.substs);
-                        required_predicates
-                            .insert(ty::OutlivesPredicate(ty.into(), outlived_region));
-                    }
+        UnpackedKind::Const(ct) => {
+            // `T: 'outlived_region` for some const generic argument `ct`.
+            // This would arise from something like:
+            //
+            // ```
+            // struct Foo<'a, const N: usize, T> {
+            //    x: &'a [T; N]
+            // }
+            // ```
+            //
+            // Here we can't decompose `N` itself (it's a value, not a
+            // type or region), but its *type* can still mention regions
+            // that need to outlive `'a`, so walk `ct.ty` the same way we
+            // would any other type.
+            insert_outlives_predicate_for_components(
+                tcx,
+                ct.ty,
+                outlived_region,
+                required_predicates,
+            )
+        }
+    }
+}
 
-                    Component::EscapingProjection(_) => {
-                        // As above, but the projection involves
-                        // late-bound regions.  Therefore, the WF
-                        // requirement is not checked in type definition
-                        // but at fn call site, so ignore it.
-                        //
-                        // ```
-                        // struct Foo<'a, T: Iterator> {
-                        //    x: for<'b> fn(<&'b T as Iterator>::Item)
-                        //              //  ^^^^^^^^^^^^^^^^^^^^^^^^^
-                        // }
-                        // ```
-                        //
-                        // Since `'b` is not in scope on `Foo`, can't
-                        // do anything here, ignore it.
-                    }
+/// Decomposes `ty` into its outlives components (regions, type
+/// parameters, and alias types) and, for each one, requires that it
+/// outlives `outlived_region`. Returns `true` if any new predicate
+/// was added.
+fn insert_outlives_predicate_for_components<'tcx>(
+    tcx: TyCtxt<'_, 'tcx, 'tcx>,
+    ty: Ty<'tcx>,
+    outlived_region: Region<'tcx>,
+    required_predicates: &mut RequiredPredicates<'tcx>,
+) -> bool {
+    let mut changed = false;
+    for component in tcx.outlives_components(ty) {
+        match component {
+            Component::Region(r) => {
+                // This would arise from something like:
+                //
+                // ```
+                // struct Foo<'a, 'b> {
+                //    x:  &'a &'b u32
+                // }
+                // ```
+                //
+                // Here `outlived_region = 'a` and `kind = &'b
+                // u32`.  Decomposing `&'b u32` into
+                // components would yield `'b`, and we add the
+                // where clause that `'b: 'a`.
+                changed |=
+                    insert_outlives_predicate(tcx, r.into(), outlived_region, required_predicates);
+            }
 
-                    Component::UnresolvedInferenceVariable(_) => bug!("not using infcx"),
-                }
+            Component::Param(param_ty) => {
+                // param_ty: ty::ParamTy
+                // This would arise from something like:
+                //
+                // ```
+                // struct Foo<'a, U> {
+                //    x:  &'a Vec<U>
+                // }
+                // ```
+                //
+                // Here `outlived_region = 'a` and `kind =
+                // Vec<U>`.  Decomposing `Vec<U>` into
+                // components would yield `U`, and we add the
+                // where clause that `U: 'a`.
+                let ty: Ty<'tcx> = param_ty.to_ty(tcx);
+                changed |=
+                    insert_required_predicate(ty.into(), outlived_region, required_predicates);
             }
-        }
 
-        UnpackedKind::Lifetime(r) => {
-            if !is_free_region(r) {
-                return;
+            Component::Projection(proj_ty) => {
+                // This would arise from something like:
+                //
+                // ```
+                // struct Foo<'a, T: Iterator> {
+                //    x:  &'a <T as Iterator>::Item
+                // }
+                // ```
+                //
+                // Here we want to add an explicit `where <T as Iterator>::Item: 'a`.
+                let ty: Ty<'tcx> = tcx.mk_projection(proj_ty.item_def_id, proj_ty.substs);
+                changed |=
+                    insert_required_predicate(ty.into(), outlived_region, required_predicates);
             }
-            required_predicates.insert(ty::OutlivesPredicate(kind, outlived_region));
+
+            Component::EscapingProjection(substs) => {
+                // As above, but the projection involves late-bound
+                // regions, so we can't name the projection itself on
+                // `Foo`'s header:
+                //
+                // ```
+                // struct Foo<'a, T: Iterator> {
+                //    x: for<'b> fn(<&'b T as Iterator>::Item)
+                //              //  ^^^^^^^^^^^^^^^^^^^^^^^^^
+                // }
+                // ```
+                //
+                // Since `'b` is not in scope on `Foo`, instead of
+                // dropping the requirement entirely (as before), fall
+                // back to the conservative rule: require that every
+                // component of the projection's substitution outlives
+                // `'a`.
+                for subst in substs {
+                    match subst.unpack() {
+                        UnpackedKind::Lifetime(lt) => {
+                            changed |= insert_outlives_predicate(
+                                tcx,
+                                lt.into(),
+                                outlived_region,
+                                required_predicates,
+                            );
+                        }
+
+                        UnpackedKind::Type(ty) => {
+                            changed |= insert_outlives_predicate(
+                                tcx,
+                                ty.into(),
+                                outlived_region,
+                                required_predicates,
+                            );
+                        }
+
+                        UnpackedKind::Const(ct) => {
+                            changed |= insert_outlives_predicate(
+                                tcx,
+                                ct.into(),
+                                outlived_region,
+                                required_predicates,
+                            );
+                        }
+                    }
+                }
+            }
+
+            Component::UnresolvedInferenceVariable(_) => bug!("not using infcx"),
         }
     }
+    changed
 }
 
+/// Records that `kind: outlived_region` in `required_predicates`,
+/// deduplicating against the regions already required for `kind`.
+/// Returns `true` if this was a new entry.
+fn insert_required_predicate<'tcx>(
+    kind: Kind<'tcx>,
+    outlived_region: Region<'tcx>,
+    required_predicates: &mut RequiredPredicates<'tcx>,
+) -> bool {
+    let regions = required_predicates.entry(kind).or_insert_with(SmallVec::new);
+    if regions.contains(&outlived_region) {
+        false
+    } else {
+        regions.push(outlived_region);
+        true
+    }
+}
+
+// Note: RFC 599 object-lifetime defaults are *not* derived from
+// `RequiredPredicates` here. `inferred_outlives_of` (which this module
+// feeds) is computed from the ADT's already-lowered field types, but
+// object-lifetime-default resolution has to happen earlier, during
+// the HIR-to-`Ty` lowering of those very fields -- it's what lets an
+// elided `&T`/`Box<dyn Trait>` field lower to a concrete `Ty` in the
+// first place. Deriving it from this query would mean deriving the
+// query's own input from its output. The actual RFC 599 algorithm
+// lives in `rustc::middle::resolve_lifetime`, operating on each type
+// parameter's *explicit* bounds from HIR, independently of the
+// implicit-outlives inference performed in this file.
+
 fn is_free_region(region: Region<'_>) -> bool {
     // First, screen for regions that might appear in a type header.
     match region {
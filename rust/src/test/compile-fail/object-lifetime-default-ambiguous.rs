@@ -0,0 +1,39 @@
+#![allow(dead_code)]
+
+// Test the RFC 599 object-lifetime defaults of a struct's type
+// parameters, which come from each parameter's explicit lifetime
+// bounds: a type parameter with exactly one distinct bounding region
+// gets that region as its default, so a trait object in that position
+// doesn't need an explicit lifetime; a type parameter with two or more
+// distinct bounding regions is ambiguous and must be spelled out
+// explicitly. Outlives requirements that are only inferred from the
+// fields, with no bound written on the parameter, are not consulted.
+
+trait Trait1 {}
+
+// `T` has a single explicit bounding region (`'a`), so `dyn Trait1`
+// here defaults to `dyn Trait1 + 'a` and needs no annotation.
+struct Single<'a, T: ?Sized + 'a> {
+    x: &'a T,
+}
+
+fn single<'a>(x: &'a (dyn Trait1 + 'a)) -> Single<'a, dyn Trait1> {
+    Single { x }
+}
+
+// `T` has two distinct explicit bounding regions (`'a` and `'b`), so
+// the object lifetime default is ambiguous.
+struct Ambiguous<'a, 'b, T: ?Sized + 'a + 'b> {
+    x: &'a T,
+    y: &'b T,
+}
+
+fn ambiguous<'a, 'b>(
+    x: &'a (dyn Trait1 + 'a),
+    y: &'b (dyn Trait1 + 'b),
+) -> Ambiguous<'a, 'b, dyn Trait1> {
+    //~^ ERROR the lifetime bound for this object type cannot be deduced from context; please supply an explicit bound
+    Ambiguous { x, y }
+}
+
+fn main() {}
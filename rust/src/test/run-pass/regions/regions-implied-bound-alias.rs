@@ -0,0 +1,19 @@
+// Test that the implied outlives-bound for a struct field whose type
+// is an associated-type projection is inferred automatically, so that
+// `Foo` below doesn't need an explicit `where <T as Iterator>::Item: 'a`.
+
+struct Foo<'a, T: Iterator> {
+    x: &'a <T as Iterator>::Item,
+}
+
+fn foo<'a, T: Iterator>(x: &'a T::Item) -> Foo<'a, T> {
+    Foo { x }
+}
+
+fn main() {
+    let v = vec![1, 2, 3];
+    let mut it = v.into_iter();
+    let first = it.next().unwrap();
+    let f = foo::<std::vec::IntoIter<i32>>(&first);
+    assert_eq!(*f.x, 1);
+}
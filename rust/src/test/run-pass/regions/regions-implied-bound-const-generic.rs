@@ -0,0 +1,29 @@
+#![feature(const_generics)]
+#![allow(incomplete_features)]
+
+// Test that the implied outlives-bound inference walks through
+// const-generic arguments without an ICE: `Foo` below has a field
+// behind a const-generic array length, and the struct header should
+// still end up with the requirements implied by `ct.ty`.
+//
+// This does *not* exercise a region actually being extracted out of
+// `ct.ty`: today's const generics only accept structural-match scalar
+// types (`usize` here) as the type of a const parameter, and a scalar
+// type's outlives components are always empty, so there is currently
+// no way to write a const parameter whose type mentions a lifetime.
+// If a future extension to const generics allows non-scalar const
+// parameter types that can carry a region, add a case for that here.
+
+struct Foo<'a, T, const N: usize> {
+    x: &'a [T; N],
+}
+
+fn foo<'a, T, const N: usize>(x: &'a [T; N]) -> Foo<'a, T, N> {
+    Foo { x }
+}
+
+fn main() {
+    let arr = [1u8, 2, 3];
+    let f = foo(&arr);
+    assert_eq!(f.x.len(), 3);
+}
@@ -0,0 +1,19 @@
+#![feature(rustc_attrs)]
+
+// Borrowing and mutating a field of a layout-constrained type through
+// interior mutability is exactly what `Cell` is for outside of const
+// evaluation, so none of this should trigger the const-only
+// layout-constrained-field check.
+
+use std::cell::Cell;
+
+#[rustc_layout_scalar_valid_range_start(1)]
+#[repr(transparent)]
+pub(crate) struct NonZero<T>(pub(crate) T);
+
+fn main() {
+    let x = unsafe { NonZero(Cell::new(1)) };
+    let y = &x.0;
+    y.set(2);
+    assert_eq!(x.0.get(), 2);
+}
@@ -17,5 +17,6 @@ const fn foo() -> NonZero<Cell<u32>> {
 const fn bar() -> NonZero<Cell<u32>> {
     let mut x = unsafe { NonZero(Cell::new(1)) };
     let y = unsafe { &x.0 }; //~ ERROR cannot borrow a constant which may contain interior mut
+    //~^ ERROR borrow of layout constrained field with interior mutability
     unsafe { NonZero(Cell::new(1)) }
 }